@@ -0,0 +1,56 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Runs `tick` on a background thread every `interval` until dropped.
+pub struct Autosave {
+    stop: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Autosave {
+    pub fn new(interval: Duration, mut tick: impl FnMut() + Send + 'static) -> Self {
+        let (stop, stop_receiver) = mpsc::channel();
+        let handle = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+                tick();
+            }
+        });
+        Self {
+            stop: Some(stop),
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_ticks_until_dropped() {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&ticks);
+        let autosave = Autosave::new(Duration::from_millis(10), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        });
+        thread::sleep(Duration::from_millis(100));
+        drop(autosave);
+
+        let after_drop = ticks.load(Ordering::SeqCst);
+        assert!(after_drop > 0);
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(ticks.load(Ordering::SeqCst), after_drop);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::tracker::Tracker;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct BudgetAlert {
+    pub host: String,
+    pub limit_ms: u64,
+    pub used_ms: u64,
+}
+
+/// Daily time limits per host. Each limit is reported once on the day it is crossed; the
+/// set of reported hosts starts over when the day changes.
+pub(crate) struct BudgetAlerts {
+    limits: HashMap<String, u64>,
+    notified: HashSet<String>,
+    day: Option<NaiveDate>,
+}
+
+impl BudgetAlerts {
+    pub fn new(limits: HashMap<String, u64>) -> Self {
+        Self {
+            limits,
+            notified: HashSet::new(),
+            day: None,
+        }
+    }
+
+    pub fn check(&mut self, tracker: &Tracker, today: NaiveDate) -> Vec<BudgetAlert> {
+        if self.day != Some(today) {
+            self.notified.clear();
+            self.day = Some(today);
+        }
+        let mut alerts = Vec::new();
+        for (host, limit_ms) in &self.limits {
+            if self.notified.contains(host) {
+                continue;
+            }
+            let used_ms = tracker.time_on(host, today);
+            if used_ms >= *limit_ms {
+                self.notified.insert(host.clone());
+                alerts.push(BudgetAlert {
+                    host: host.clone(),
+                    limit_ms: *limit_ms,
+                    used_ms,
+                });
+            }
+        }
+        alerts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    #[test]
+    fn test_crossed_limit_alerts_once_per_day() {
+        let mut tracker = Tracker::new("budget".to_string());
+        tracker.track_tab_focused("https://video.com/", 1).unwrap();
+        sleep(Duration::from_millis(10));
+        let mut alerts = BudgetAlerts::new(HashMap::from([
+            ("video.com".to_string(), 5),
+            ("news.com".to_string(), 5),
+        ]));
+        let today = chrono::Local::now().date_naive();
+
+        let first = alerts.check(&tracker, today);
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].host, "video.com");
+        assert!(first[0].used_ms >= 10);
+        assert!(alerts.check(&tracker, today).is_empty());
+
+        let tomorrow = today.succ_opt().unwrap();
+        assert_eq!(alerts.check(&tracker, tomorrow).len(), 1);
+    }
+}
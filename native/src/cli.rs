@@ -0,0 +1,140 @@
+use crate::session_loader::PersistenceError;
+use crate::session_store::SessionStore;
+use crate::tracker::Tracker;
+use std::io::Write;
+
+pub const USAGE: &str = "\
+Usage: native [OPTION]
+
+Without an option, runs as the browser's native messaging host.
+
+Options:
+  --version              Print the version and exit
+  --help                 Print this help and exit
+  --dump-session <name>  Print the tracking data of a saved session as JSON";
+
+#[derive(Debug, thiserror::Error)]
+pub enum CliError {
+    #[error("--dump-session requires a session name")]
+    MissingSessionName,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Version,
+    Help,
+    DumpSession(String),
+    /// No recognized option: run the messaging loop. Browsers pass their own arguments,
+    /// e.g. the extension origin or `--parent-window=0`, which are ignored.
+    Serve,
+}
+
+/// Only the first argument selects a command, so a browser argument is never mistaken
+/// for one further along.
+pub fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Command, CliError> {
+    Ok(match args.next().as_deref() {
+        Some("--version") => Command::Version,
+        Some("--help") => Command::Help,
+        Some("--dump-session") => {
+            Command::DumpSession(args.next().ok_or(CliError::MissingSessionName)?)
+        }
+        _ => Command::Serve,
+    })
+}
+
+pub fn version_line() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+}
+
+pub fn dump_session(
+    session_store: &dyn SessionStore,
+    session_name: &str,
+    out: &mut impl Write,
+) -> Result<(), PersistenceError> {
+    let session = session_store.load_session(session_name)?;
+    let mut tracker =
+        Tracker::from_serialized(session.session_name, session.data, false, session.paused);
+    let (data, _) = tracker.collect_tracking_data_cached();
+    let dump = serde_json::json!({
+        "session_name": tracker.get_session_name(),
+        "data": data,
+    });
+    serde_json::to_writer_pretty(&mut *out, &dump)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::TestClock;
+    use crate::session_store::InMemorySessionStore;
+    use std::sync::Arc;
+
+    fn parse(args: &[&str]) -> Result<Command, CliError> {
+        parse_args(args.iter().map(ToString::to_string))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert_eq!(parse(&["--version"]).unwrap(), Command::Version);
+        assert_eq!(parse(&["--help"]).unwrap(), Command::Help);
+        assert_eq!(
+            parse(&["--dump-session", "work"]).unwrap(),
+            Command::DumpSession("work".to_string())
+        );
+        assert!(matches!(
+            parse(&["--dump-session"]),
+            Err(CliError::MissingSessionName)
+        ));
+        assert_eq!(parse(&[]).unwrap(), Command::Serve);
+        assert_eq!(
+            parse(&["chrome-extension://abcdef/", "--parent-window=0"]).unwrap(),
+            Command::Serve
+        );
+        assert_eq!(
+            parse(&[
+                "/home/user/.mozilla/native-messaging-hosts/timer.json",
+                "--version"
+            ])
+            .unwrap(),
+            Command::Serve
+        );
+    }
+
+    #[test]
+    fn test_version_line() {
+        assert!(version_line().ends_with(env!("CARGO_PKG_VERSION")));
+    }
+
+    #[test]
+    fn test_dump_session() {
+        let store = InMemorySessionStore::default();
+        let clock = TestClock::new(1_000);
+        let mut tracker = Tracker::new_with_clock("work".to_string(), Arc::new(clock.clone()));
+        tracker.track_tab_focused("https://a.com/docs", 1).unwrap();
+        clock.advance(500);
+        tracker.track_tab_closed("https://a.com/docs", 1).unwrap();
+        store
+            .save_session(&tracker.serialize_session(false))
+            .unwrap();
+
+        let mut out = Vec::new();
+        dump_session(&store, "work", &mut out).unwrap();
+        let dump: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(dump["session_name"], "work");
+        let paths: Vec<_> = dump["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|row| row["path"].as_str().unwrap())
+            .collect();
+        assert_eq!(paths, vec!["a.com/docs"]);
+        assert_eq!(dump["data"][0]["aggregate_time"], 500);
+
+        assert!(matches!(
+            dump_session(&store, "missing", &mut Vec::new()),
+            Err(PersistenceError::SessionNotFound(_))
+        ));
+    }
+}
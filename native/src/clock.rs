@@ -0,0 +1,49 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the tracker's notion of "now", in milliseconds since the Unix epoch.
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(1754316069547) // 2025.08.04 because why not?
+    }
+}
+
+/// Manually driven clock for tests. Clones share the same time, so a test can keep one
+/// handle and advance the clock of a tracker it has handed the other to.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct TestClock {
+    now: std::sync::Arc<std::sync::atomic::AtomicU64>,
+}
+
+#[cfg(test)]
+impl TestClock {
+    pub fn new(start_ms: u64) -> Self {
+        Self {
+            now: std::sync::Arc::new(std::sync::atomic::AtomicU64::new(start_ms)),
+        }
+    }
+
+    pub fn advance(&self, ms: u64) {
+        self.now.fetch_add(ms, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn set(&self, now_ms: u64) {
+        self.now.store(now_ms, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+impl Clock for TestClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
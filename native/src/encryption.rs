@@ -0,0 +1,92 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::Argon2;
+
+const MAGIC: &[u8] = b"BTENC1";
+const NONCE_LENGTH: usize = 12;
+pub const SALT_LENGTH: usize = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum EncryptionError {
+    #[error("Key derivation failed: {0}")]
+    KeyDerivation(String),
+    #[error("Encryption failed")]
+    Encrypt,
+    #[error("Decryption failed: wrong passphrase or corrupted file")]
+    Decrypt,
+}
+
+/// AES-256-GCM with a key derived from a passphrase via Argon2. Every encrypted blob is
+/// `MAGIC || nonce || ciphertext`, with a fresh random nonce per call.
+pub struct SessionCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SessionCipher {
+    pub fn from_passphrase(passphrase: &str, salt: &[u8]) -> Result<Self, EncryptionError> {
+        let mut key = [0u8; 32];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+        Ok(Self {
+            cipher: Aes256Gcm::new(&key.into()),
+        })
+    }
+
+    pub fn generate_salt() -> [u8; SALT_LENGTH] {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        salt
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| EncryptionError::Encrypt)?;
+
+        let mut output = Vec::with_capacity(MAGIC.len() + NONCE_LENGTH + ciphertext.len());
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&ciphertext);
+        Ok(output)
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, EncryptionError> {
+        let body = data.strip_prefix(MAGIC).ok_or(EncryptionError::Decrypt)?;
+        if body.len() < NONCE_LENGTH {
+            return Err(EncryptionError::Decrypt);
+        }
+        let (nonce, ciphertext) = body.split_at(NONCE_LENGTH);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| EncryptionError::Decrypt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip_and_wrong_key() {
+        let salt = SessionCipher::generate_salt();
+        let cipher = SessionCipher::from_passphrase("correct horse", &salt).unwrap();
+        let encrypted = cipher.encrypt(b"{\"session_name\":\"x\"}").unwrap();
+
+        assert!(!encrypted.windows(12).any(|w| w == b"session_name"));
+        assert_eq!(
+            cipher.decrypt(&encrypted).unwrap(),
+            b"{\"session_name\":\"x\"}"
+        );
+
+        let wrong = SessionCipher::from_passphrase("battery staple", &salt).unwrap();
+        assert!(matches!(
+            wrong.decrypt(&encrypted),
+            Err(EncryptionError::Decrypt)
+        ));
+    }
+}
@@ -1,10 +1,18 @@
-use chrono::Local;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Local, Utc};
 use std::fs::{File, OpenOptions};
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 
+const DEFAULT_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Clone)]
 pub struct Logger {
-    log_file_path: PathBuf,
+    // `None` when no log directory was usable; messages then only go to stderr.
+    log_file_path: Option<PathBuf>,
+    timestamp_format: String,
+    utc: bool,
+    fallback_in_use: bool,
 }
 
 impl Logger {
@@ -16,17 +24,74 @@ impl Logger {
             File::create(&log_file_path)?;
         }
 
-        Ok(Logger { log_file_path })
+        Ok(Logger {
+            log_file_path: Some(log_file_path),
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+            utc: false,
+            fallback_in_use: false,
+        })
+    }
+
+    /// Never fails: if `directory_path` is unusable (e.g. a read-only working directory),
+    /// logs go to a directory under the system temp dir, and failing that to stderr only.
+    pub fn with_fallback<P: AsRef<Path>>(directory_path: P) -> Self {
+        Self::with_fallback_directories(
+            directory_path.as_ref(),
+            &std::env::temp_dir().join("browser_timer_logs"),
+        )
+    }
+
+    fn with_fallback_directories(preferred: &Path, fallback: &Path) -> Self {
+        Self::new(preferred).unwrap_or_else(|_| {
+            let mut logger = Self::new(fallback).unwrap_or_else(|_| Self::stderr_only());
+            logger.fallback_in_use = true;
+            logger
+        })
+    }
+
+    fn stderr_only() -> Self {
+        Logger {
+            log_file_path: None,
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_string(),
+            utc: false,
+            fallback_in_use: true,
+        }
+    }
+
+    pub fn with_timestamp_format(mut self, format: String) -> io::Result<Self> {
+        if format.is_empty() || StrftimeItems::new(&format).any(|item| item == Item::Error) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid log timestamp format: {}", format),
+            ));
+        }
+        self.timestamp_format = format;
+        Ok(self)
+    }
+
+    pub fn with_utc(mut self, utc: bool) -> Self {
+        self.utc = utc;
+        self
     }
 
-    fn log(&self, message: &str) -> io::Result<()> {
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+    fn format_timestamp(&self, now: DateTime<Utc>) -> String {
+        if self.utc {
+            now.format(&self.timestamp_format).to_string()
+        } else {
+            now.with_timezone(&Local)
+                .format(&self.timestamp_format)
+                .to_string()
+        }
+    }
+
+    fn log(&self, log_file_path: &Path, message: &str) -> io::Result<()> {
+        let timestamp = self.format_timestamp(Utc::now());
         let log_entry = format!("[{}] {}\n", timestamp, message);
 
         let mut file = OpenOptions::new()
             .create(true)
             .append(true)
-            .open(&self.log_file_path)?;
+            .open(log_file_path)?;
 
         file.write_all(log_entry.as_bytes())?;
         file.flush()?;
@@ -35,7 +100,11 @@ impl Logger {
     }
 
     fn log_or_console(&self, message: &str) {
-        if let Err(e) = self.log(message) {
+        let Some(log_file_path) = &self.log_file_path else {
+            eprintln!("[{}] {}", self.format_timestamp(Utc::now()), message);
+            return;
+        };
+        if let Err(e) = self.log(log_file_path, message) {
             eprintln!("Failed to log to file: {}", e);
             eprintln!("{}", message);
         }
@@ -45,13 +114,167 @@ impl Logger {
         self.log_or_console(&format!("INFO: {}", message))
     }
 
+    pub fn warn(&self, message: &str) {
+        self.log_or_console(&format!("WARN: {}", message))
+    }
+
     pub fn error(&self, message: &str) {
         self.log_or_console(&format!("ERROR: {}", message))
     }
     pub fn debug(&self, message: &str) {
         self.log_or_console(&format!("DEBUG: {}", message))
     }
-    pub fn log_file_path(&self) -> &Path {
-        &self.log_file_path
+    pub fn log_file_path(&self) -> Option<&Path> {
+        self.log_file_path.as_deref()
     }
-}
\ No newline at end of file
+
+    /// Empties the log file and removes rotated copies such as `app.log.1`. Each write
+    /// opens the file in append mode, so later messages start at the beginning again.
+    pub fn clear(&self) -> io::Result<()> {
+        let Some(log_file_path) = &self.log_file_path else {
+            return Ok(());
+        };
+        OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(log_file_path)?;
+        let (Some(directory), Some(file_name)) = (
+            log_file_path.parent(),
+            log_file_path.file_name().and_then(|name| name.to_str()),
+        ) else {
+            return Ok(());
+        };
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let is_rotated = name
+                .to_str()
+                .and_then(|name| name.strip_prefix(file_name)?.strip_prefix('.'))
+                .is_some_and(|suffix| {
+                    !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit())
+                });
+            if is_rotated {
+                std::fs::remove_file(entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the preferred log directory was unusable and a fallback is in effect.
+    pub fn fallback_in_use(&self) -> bool {
+        self.fallback_in_use
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::TempDir;
+
+    fn fixed_time() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap()
+    }
+
+    #[test]
+    fn test_default_format_uses_local_time() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = Logger::new(temp_dir.path()).unwrap();
+
+        let expected = fixed_time()
+            .with_timezone(&Local)
+            .format(DEFAULT_TIMESTAMP_FORMAT)
+            .to_string();
+        assert_eq!(logger.format_timestamp(fixed_time()), expected);
+    }
+
+    #[test]
+    fn test_utc_with_custom_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = Logger::new(temp_dir.path())
+            .unwrap()
+            .with_timestamp_format("%Y-%m-%dT%H:%M:%S%.3fZ".to_string())
+            .unwrap()
+            .with_utc(true);
+
+        assert_eq!(
+            logger.format_timestamp(fixed_time()),
+            "2025-01-02T03:04:05.000Z"
+        );
+
+        logger.info("hello");
+        let contents = std::fs::read_to_string(logger.log_file_path().unwrap()).unwrap();
+        let line = contents.lines().next().unwrap();
+        assert!(line.starts_with('['));
+        assert!(line.ends_with("Z] INFO: hello"));
+    }
+
+    #[test]
+    fn test_falls_back_to_second_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let blocker = temp_dir.path().join("blocker");
+        std::fs::write(&blocker, "not a directory").unwrap();
+        let fallback = temp_dir.path().join("fallback");
+
+        let logger = Logger::with_fallback_directories(&blocker.join("logs"), &fallback);
+
+        assert_eq!(
+            logger.log_file_path(),
+            Some(fallback.join("app.log").as_path())
+        );
+        assert!(logger.fallback_in_use());
+        logger.info("still logging");
+        let contents = std::fs::read_to_string(fallback.join("app.log")).unwrap();
+        assert!(contents.contains("INFO: still logging"));
+    }
+
+    #[test]
+    fn test_stderr_only_when_no_directory_is_usable() {
+        let temp_dir = TempDir::new().unwrap();
+        let blocker = temp_dir.path().join("blocker");
+        std::fs::write(&blocker, "not a directory").unwrap();
+
+        let logger =
+            Logger::with_fallback_directories(&blocker.join("logs"), &blocker.join("fallback"));
+
+        assert_eq!(logger.log_file_path(), None);
+        logger.error("goes to stderr");
+    }
+
+    #[test]
+    fn test_clear_empties_log_and_removes_rotated_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let logger = Logger::new(temp_dir.path()).unwrap();
+        logger.info("before clear");
+        std::fs::write(temp_dir.path().join("app.log.1"), "old").unwrap();
+        std::fs::write(temp_dir.path().join("app.log.bak"), "kept").unwrap();
+
+        logger.clear().unwrap();
+        let log_file_path = logger.log_file_path().unwrap();
+        assert_eq!(std::fs::read_to_string(log_file_path).unwrap(), "");
+        assert!(!temp_dir.path().join("app.log.1").exists());
+        assert!(temp_dir.path().join("app.log.bak").exists());
+
+        logger.info("after clear");
+        logger.info("and again");
+        let contents = std::fs::read_to_string(log_file_path).unwrap();
+        assert_eq!(contents.lines().count(), 2);
+        assert!(
+            contents
+                .lines()
+                .next()
+                .unwrap()
+                .ends_with("INFO: after clear")
+        );
+    }
+
+    #[test]
+    fn test_invalid_format_fails_fast() {
+        let temp_dir = TempDir::new().unwrap();
+        let result = Logger::new(temp_dir.path())
+            .unwrap()
+            .with_timestamp_format("%Y-%Q".to_string());
+        assert!(result.is_err());
+    }
+}
@@ -1,15 +1,135 @@
+use crate::cli::Command;
 use crate::logger::Logger;
+use crate::metrics::MetricsWriter;
+use crate::session_loader::{SessionFormat, SessionLoader, log_directory};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
 
+mod autosave;
+mod budget;
+mod cli;
+mod clock;
+mod encryption;
 mod logger;
 mod message_handler;
+mod metrics;
+mod productivity;
+mod schedule;
 mod session_loader;
+mod session_store;
 mod tracker;
 
+fn env_flag(name: &str) -> bool {
+    std::env::var(name).is_ok_and(|value| value == "1")
+}
+
+// Unset and unparsable values both yield `None`.
+fn env_parse<T: FromStr>(name: &str) -> Option<T> {
+    std::env::var(name).ok()?.parse().ok()
+}
+
+// An invalid timestamp format falls back to the default one, with the reason logged.
+fn create_logger() -> Logger {
+    let log_directory = log_directory().unwrap_or_else(|_| "./logs".into());
+    let logger = Logger::with_fallback(log_directory).with_utc(env_flag("BROWSER_TIMER_LOG_UTC"));
+    let Ok(format) = std::env::var("BROWSER_TIMER_LOG_TIME_FORMAT") else {
+        return logger;
+    };
+    let fallback = logger.clone();
+    logger.with_timestamp_format(format).unwrap_or_else(|e| {
+        fallback.warn(format!("{}. Using the default format", e).as_str());
+        fallback
+    })
+}
+
+fn query_allowlist() -> Vec<String> {
+    std::env::var("BROWSER_TIMER_QUERY_ALLOWLIST")
+        .map(|list| {
+            list.split(',')
+                .map(str::trim)
+                .filter(|param| !param.is_empty())
+                .map(ToString::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn daily_limits() -> HashMap<String, u64> {
+    std::env::var("BROWSER_TIMER_DAILY_LIMITS")
+        .map(|list| {
+            list.split(',')
+                .filter_map(|entry| entry.split_once('='))
+                .filter_map(|(host, limit)| {
+                    Some((host.trim().to_string(), limit.trim().parse::<u64>().ok()?))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn create_metrics_writer(session_loader: &SessionLoader) -> Option<std::io::Result<MetricsWriter>> {
+    let interval_secs =
+        env_parse::<u64>("BROWSER_TIMER_STATS_INTERVAL_SECS").filter(|secs| *secs > 0)?;
+    let save_directory = session_loader.get_save_directory();
+    let data_directory = save_directory.parent().unwrap_or(save_directory);
+    Some(MetricsWriter::new(
+        data_directory,
+        Duration::from_secs(interval_secs),
+    ))
+}
+
+// Leaves recovering interrupted saves to the caller, so read-only commands can skip it.
+fn create_session_loader() -> Result<SessionLoader, session_loader::PersistenceError> {
+    SessionLoader::default_save_directory()
+        .and_then(SessionLoader::new)
+        .and_then(|loader| {
+            let format = std::env::var("BROWSER_TIMER_SESSION_FORMAT")
+                .ok()
+                .and_then(|format| SessionFormat::parse(&format))
+                .unwrap_or_default();
+            let loader = loader
+                .with_strict_name_check(!env_flag("BROWSER_TIMER_LENIENT_SESSION_NAMES"))
+                .with_format(format);
+            match std::env::var("BROWSER_TIMER_PASSPHRASE") {
+                Ok(passphrase) => loader.with_passphrase(&passphrase),
+                Err(_) => Ok(loader),
+            }
+        })
+}
+
+fn dump_session(session_name: &str) -> Result<(), session_loader::PersistenceError> {
+    let session_loader = create_session_loader()?;
+    cli::dump_session(&session_loader, session_name, &mut std::io::stdout().lock())
+}
+
 fn main() {
-    let logger = Logger::new("./logs").unwrap(); // no logger, no app
+    match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Command::Version) => return println!("{}", cli::version_line()),
+        Ok(Command::Help) => return println!("{}", cli::USAGE),
+        Ok(Command::DumpSession(session_name)) => {
+            if let Err(e) = dump_session(&session_name) {
+                eprintln!("Failed to dump session {}: {}", session_name, e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Ok(Command::Serve) => {}
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    }
+    let logger = create_logger();
     logger.info("Native messaging host starting...");
-    eprintln!("Logging to file {}", logger.log_file_path().display());
-    let loader = session_loader::SessionLoader::with_default_directory();
+    match logger.log_file_path() {
+        Some(log_file_path) => eprintln!("Logging to file {}", log_file_path.display()),
+        None => eprintln!("No usable log directory, logging to stderr only"),
+    }
+    let loader = create_session_loader().and_then(|loader| {
+        loader.recover_temp_files()?;
+        Ok(loader)
+    });
     if let Ok(session_loader) = loader {
         logger.info(
             format!(
@@ -18,7 +138,84 @@ fn main() {
             )
             .as_str(),
         );
-        let mut host = message_handler::NativeMessagingHost::new(session_loader, &logger);
+        let metrics = create_metrics_writer(&session_loader);
+        let mut host = message_handler::NativeMessagingHost::new(session_loader, &logger)
+            .with_query_allowlist(query_allowlist())
+            .with_record_full_url(env_flag("BROWSER_TIMER_RECORD_FULL_URL"))
+            .with_nest_subdomains(env_flag("BROWSER_TIMER_NEST_SUBDOMAINS"))
+            .with_include_port(env_flag("BROWSER_TIMER_INCLUDE_PORT"))
+            .with_distinguish_scheme(env_flag("BROWSER_TIMER_DISTINGUISH_SCHEME"))
+            .with_track_fragment(env_flag("BROWSER_TIMER_TRACK_FRAGMENT"))
+            .with_switch_on_start(env_flag("BROWSER_TIMER_SWITCH_ON_START"))
+            .with_single_focus(env_flag("BROWSER_TIMER_SINGLE_FOCUS"))
+            .with_strict_events(env_flag("BROWSER_TIMER_STRICT_EVENTS"));
+        if let Some(prefix) = std::env::var("BROWSER_TIMER_DAILY_SESSION")
+            .ok()
+            .filter(|prefix| !prefix.is_empty())
+        {
+            host = host.with_daily_rotation(prefix);
+        }
+        if env_flag("BROWSER_TIMER_AUTO_START") {
+            let session_name = std::env::var("BROWSER_TIMER_AUTO_START_SESSION")
+                .unwrap_or_else(|_| "default".to_string());
+            host = host.with_auto_start(session_name);
+        }
+        if let Some(max_url_len) = env_parse::<usize>("BROWSER_TIMER_MAX_URL_LEN") {
+            host = host.with_max_url_len(max_url_len);
+        }
+        if let Some(round_to_ms) = env_parse::<u64>("BROWSER_TIMER_ROUND_TO_MS") {
+            host = host.with_round_to_ms(round_to_ms);
+        }
+        if let Some(warmup_ms) = env_parse::<u64>("BROWSER_TIMER_WARMUP_MS") {
+            host = host.with_warmup_ms(warmup_ms);
+        }
+        if let Some(inactive_ttl_ms) = env_parse::<u64>("BROWSER_TIMER_INACTIVE_TTL_MS") {
+            host = host.with_inactive_ttl_ms(inactive_ttl_ms);
+        }
+        if let Some(max_nodes) = env_parse::<usize>("BROWSER_TIMER_MAX_NODES") {
+            host = host.with_max_nodes(max_nodes);
+        }
+        if let Some(max_url_segments) = env_parse::<usize>("BROWSER_TIMER_MAX_URL_SEGMENTS") {
+            host = host.with_max_url_segments(max_url_segments);
+        }
+        if let Some(event_batch_size) = env_parse::<usize>("BROWSER_TIMER_EVENT_BATCH_SIZE") {
+            host = host.with_event_batch_size(event_batch_size);
+        }
+        if let Some(max_len) =
+            env_parse::<usize>("BROWSER_TIMER_PROTOCOL_TRACE").filter(|max_len| *max_len > 0)
+        {
+            host = host.with_protocol_trace(max_len);
+        }
+        if let Some(save_attempts) = env_parse::<u32>("BROWSER_TIMER_SAVE_ATTEMPTS") {
+            host = host.with_save_attempts(save_attempts);
+        }
+        if let Some(autosave_secs) =
+            env_parse::<u64>("BROWSER_TIMER_AUTOSAVE_SECS").filter(|secs| *secs > 0)
+        {
+            host = host.with_autosave_interval(Duration::from_secs(autosave_secs));
+        }
+        if let Some(session_gap_ms) = env_parse::<u64>("BROWSER_TIMER_SESSION_GAP_MS") {
+            host = host.with_session_gap_ms(session_gap_ms);
+        }
+        if let Ok(spec) = std::env::var("BROWSER_TIMER_TRACKING_SCHEDULE") {
+            match schedule::TrackingSchedule::parse(&spec) {
+                Ok(schedule) => host = host.with_tracking_schedule(schedule),
+                Err(e) => {
+                    logger.error(format!("Ignoring the tracking schedule. Reason {}", e).as_str())
+                }
+            }
+        }
+        let limits = daily_limits();
+        if !limits.is_empty() {
+            host = host.with_daily_limits(limits);
+        }
+        match metrics {
+            Some(Ok(metrics)) => host = host.with_metrics(metrics),
+            Some(Err(e)) => {
+                logger.error(format!("Failed to start the metrics writer. Reason {}", e).as_str())
+            }
+            None => {}
+        }
         host.run();
     } else {
         logger.error(
@@ -0,0 +1,137 @@
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Counters {
+    active_sessions: usize,
+    total_tracked_ms: u64,
+    messages_received: u64,
+    messages_failed: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct StatsSnapshot {
+    active_sessions: usize,
+    total_tracked_ms: u64,
+    messages_received: u64,
+    messages_failed: u64,
+    uptime_secs: u64,
+}
+
+/// Dumps host statistics to a standalone `stats.json`, so dashboards can read them
+/// without going through native messaging. The host samples the session totals every
+/// `interval`; the last sample is written again on drop.
+pub struct MetricsWriter {
+    stats_file_path: PathBuf,
+    interval: Duration,
+    counters: Mutex<Counters>,
+    started: Instant,
+}
+
+impl MetricsWriter {
+    pub fn new<P: AsRef<Path>>(directory_path: P, interval: Duration) -> io::Result<Self> {
+        let dir_path = directory_path.as_ref();
+        fs::create_dir_all(dir_path)?;
+        Ok(Self {
+            stats_file_path: dir_path.join("stats.json"),
+            interval,
+            counters: Mutex::new(Counters::default()),
+            started: Instant::now(),
+        })
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn record_message(&self, success: bool) {
+        let mut counters = self.lock_counters();
+        counters.messages_received += 1;
+        if !success {
+            counters.messages_failed += 1;
+        }
+    }
+
+    /// Stores the current session totals and writes the snapshot.
+    pub fn sample(&self, active_sessions: usize, total_tracked_ms: u64) -> io::Result<()> {
+        let counters = {
+            let mut counters = self.lock_counters();
+            counters.active_sessions = active_sessions;
+            counters.total_tracked_ms = total_tracked_ms;
+            *counters
+        };
+        self.write_snapshot(counters)
+    }
+
+    fn lock_counters(&self) -> MutexGuard<'_, Counters> {
+        self.counters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_snapshot(&self, counters: Counters) -> io::Result<()> {
+        let snapshot = StatsSnapshot {
+            active_sessions: counters.active_sessions,
+            total_tracked_ms: counters.total_tracked_ms,
+            messages_received: counters.messages_received,
+            messages_failed: counters.messages_failed,
+            uptime_secs: self.started.elapsed().as_secs(),
+        };
+        let json_data = serde_json::to_string(&snapshot)?;
+        let temp_file_path = self.stats_file_path.with_extension("json.tmp");
+        {
+            let mut file = fs::File::create(&temp_file_path)?;
+            file.write_all(json_data.as_bytes())?;
+        }
+        fs::rename(temp_file_path, &self.stats_file_path)
+    }
+}
+
+impl Drop for MetricsWriter {
+    fn drop(&mut self) {
+        let counters = *self.lock_counters();
+        let _ = self.write_snapshot(counters);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn read_stats(dir: &Path) -> serde_json::Value {
+        serde_json::from_str(&fs::read_to_string(dir.join("stats.json")).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_sample_writes_stats_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = MetricsWriter::new(temp_dir.path(), Duration::from_secs(3600)).unwrap();
+        writer.record_message(true);
+        writer.record_message(false);
+        writer.sample(1, 1500).unwrap();
+
+        let stats = read_stats(temp_dir.path());
+        assert_eq!(stats["active_sessions"], 1);
+        assert_eq!(stats["total_tracked_ms"], 1500);
+        assert_eq!(stats["messages_received"], 2);
+        assert_eq!(stats["messages_failed"], 1);
+        assert!(stats["uptime_secs"].is_u64());
+        drop(writer);
+    }
+
+    #[test]
+    fn test_final_snapshot_written_on_drop() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = MetricsWriter::new(temp_dir.path(), Duration::from_secs(3600)).unwrap();
+        writer.sample(0, 42).unwrap();
+        writer.record_message(true);
+        drop(writer);
+
+        let stats = read_stats(temp_dir.path());
+        assert_eq!(stats["messages_received"], 1);
+        assert_eq!(stats["total_tracked_ms"], 42);
+    }
+}
@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Category {
+    Productive,
+    Distracting,
+    Neutral,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub(crate) struct ProductivityScore {
+    pub productive_percent: f64,
+    pub distracting_percent: f64,
+    pub neutral_percent: f64,
+    /// Productive minus distracting share, from -100 (all distracting) to 100 (all productive).
+    pub score: f64,
+}
+
+impl ProductivityScore {
+    // A category entry also covers its subdomains, so `youtube.com` catches `www.youtube.com`.
+    fn categorize(host: &str, categories: &HashMap<String, Category>) -> Category {
+        let mut candidate = host;
+        loop {
+            if let Some(category) = categories.get(candidate) {
+                return *category;
+            }
+            match candidate.split_once('.') {
+                Some((_, parent)) => candidate = parent,
+                None => return Category::Neutral,
+            }
+        }
+    }
+
+    pub fn compute(host_times: &[(String, u64)], categories: &HashMap<String, Category>) -> Self {
+        let mut productive = 0u64;
+        let mut distracting = 0u64;
+        let mut neutral = 0u64;
+        for (host, time) in host_times {
+            let bucket = match Self::categorize(host, categories) {
+                Category::Productive => &mut productive,
+                Category::Distracting => &mut distracting,
+                Category::Neutral => &mut neutral,
+            };
+            *bucket = bucket.saturating_add(*time);
+        }
+
+        let total = productive
+            .saturating_add(distracting)
+            .saturating_add(neutral);
+        let percent = |time: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                (time as f64 * 10000.0 / total as f64).round() / 100.0
+            }
+        };
+        let productive_percent = percent(productive);
+        let distracting_percent = percent(distracting);
+        Self {
+            productive_percent,
+            distracting_percent,
+            neutral_percent: percent(neutral),
+            score: ((productive_percent - distracting_percent) * 100.0).round() / 100.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_score_over_category_map() {
+        let categories = HashMap::from([
+            ("github.com".to_string(), Category::Productive),
+            ("youtube.com".to_string(), Category::Distracting),
+        ]);
+        let host_times = vec![
+            ("github.com".to_string(), 5000),
+            ("www.youtube.com".to_string(), 2000),
+            ("news.example.org".to_string(), 3000),
+        ];
+
+        let result = ProductivityScore::compute(&host_times, &categories);
+
+        assert_eq!(
+            result,
+            ProductivityScore {
+                productive_percent: 50.0,
+                distracting_percent: 20.0,
+                neutral_percent: 30.0,
+                score: 30.0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_empty_data_scores_zero() {
+        let result = ProductivityScore::compute(&[], &HashMap::new());
+        assert_eq!(result.score, 0.0);
+        assert_eq!(result.neutral_percent, 0.0);
+    }
+}
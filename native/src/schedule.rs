@@ -0,0 +1,166 @@
+use chrono::{DateTime, Datelike, Local, NaiveTime, TimeZone, Weekday};
+use std::fmt;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScheduleError {
+    #[error("Invalid schedule window '{0}': expected e.g. 'mon-fri 09:00-17:00'")]
+    InvalidWindow(String),
+    #[error("Invalid weekday in '{0}'")]
+    InvalidWeekday(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ScheduleWindow {
+    days: Vec<Weekday>,
+    start: NaiveTime,
+    end: NaiveTime,
+}
+
+/// Local time ranges in which tabs are tracked, e.g. `mon-fri 09:00-17:00; sat 10:00-12:00`.
+/// A window without days applies to every day. Windows cannot cross midnight; split such a
+/// range into two windows instead.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TrackingSchedule {
+    windows: Vec<ScheduleWindow>,
+}
+
+/// Formats the schedule back into the syntax `parse` accepts, with day ranges expanded.
+impl fmt::Display for TrackingSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, window) in self.windows.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+            if !window.days.is_empty() {
+                let days: Vec<String> = window.days.iter().map(ToString::to_string).collect();
+                write!(f, "{} ", days.join(","))?;
+            }
+            write!(
+                f,
+                "{}-{}",
+                window.start.format("%H:%M"),
+                window.end.format("%H:%M")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl TrackingSchedule {
+    pub fn parse(spec: &str) -> Result<Self, ScheduleError> {
+        let windows = spec
+            .split(';')
+            .map(str::trim)
+            .filter(|window| !window.is_empty())
+            .map(Self::parse_window)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { windows })
+    }
+
+    fn parse_window(window: &str) -> Result<ScheduleWindow, ScheduleError> {
+        let invalid = || ScheduleError::InvalidWindow(window.to_string());
+        let (days, times) = match window.rsplit_once(' ') {
+            Some((days, times)) => (Self::parse_days(days.trim())?, times),
+            None => (Vec::new(), window),
+        };
+        let (start, end) = times.split_once('-').ok_or_else(invalid)?;
+        let parse_time =
+            |time: &str| NaiveTime::parse_from_str(time, "%H:%M").map_err(|_| invalid());
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start >= end {
+            return Err(invalid());
+        }
+        Ok(ScheduleWindow { days, start, end })
+    }
+
+    fn parse_days(days: &str) -> Result<Vec<Weekday>, ScheduleError> {
+        let parse_day = |day: &str| {
+            day.parse::<Weekday>()
+                .map_err(|_| ScheduleError::InvalidWeekday(days.to_string()))
+        };
+        let mut parsed = Vec::new();
+        for part in days.split(',') {
+            match part.split_once('-') {
+                Some((first, last)) => {
+                    let (mut day, last) = (parse_day(first)?, parse_day(last)?);
+                    parsed.push(day);
+                    while day != last {
+                        day = day.succ();
+                        parsed.push(day);
+                    }
+                }
+                None => parsed.push(parse_day(part)?),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn window_at(&self, at: DateTime<Local>) -> Option<&ScheduleWindow> {
+        let time = at.time();
+        self.windows.iter().find(|window| {
+            (window.days.is_empty() || window.days.contains(&at.weekday()))
+                && window.start <= time
+                && time < window.end
+        })
+    }
+
+    pub fn allows(&self, timestamp_ms: u64) -> bool {
+        local_time(timestamp_ms).is_some_and(|at| self.window_at(at).is_some())
+    }
+
+    /// End of the window that `timestamp_ms` falls into, in milliseconds.
+    pub fn window_end(&self, timestamp_ms: u64) -> Option<u64> {
+        let at = local_time(timestamp_ms)?;
+        let window = self.window_at(at)?;
+        let end = Local
+            .from_local_datetime(&at.date_naive().and_time(window.end))
+            .earliest()?;
+        Some(end.timestamp_millis() as u64)
+    }
+}
+
+fn local_time(timestamp_ms: u64) -> Option<DateTime<Local>> {
+    Local.timestamp_millis_opt(timestamp_ms as i64).single()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local_ms(day: u32, hour: u32, minute: u32) -> u64 {
+        // 2025-09-01 is a Monday.
+        Local
+            .with_ymd_and_hms(2025, 9, day, hour, minute, 0)
+            .unwrap()
+            .timestamp_millis() as u64
+    }
+
+    #[test]
+    fn test_parse_and_allows() {
+        let schedule = TrackingSchedule::parse("mon-fri 09:00-17:00; sat,sun 10:00-12:00").unwrap();
+        assert!(schedule.allows(local_ms(1, 9, 0)));
+        assert!(!schedule.allows(local_ms(1, 17, 0)));
+        assert!(!schedule.allows(local_ms(6, 9, 30)));
+        assert!(schedule.allows(local_ms(7, 11, 59)));
+        assert_eq!(
+            schedule.window_end(local_ms(3, 12, 0)),
+            Some(local_ms(3, 17, 0))
+        );
+        assert_eq!(schedule.window_end(local_ms(3, 18, 0)), None);
+
+        let daily = TrackingSchedule::parse("08:00-20:00").unwrap();
+        assert!(daily.allows(local_ms(6, 8, 0)));
+
+        assert_eq!(daily.to_string(), "08:00-20:00");
+        assert_eq!(
+            TrackingSchedule::parse("mon-wed 09:00-17:00;sun 10:00-12:00")
+                .unwrap()
+                .to_string(),
+            "Mon,Tue,Wed 09:00-17:00; Sun 10:00-12:00"
+        );
+
+        assert!(TrackingSchedule::parse("mon-fri 17:00-09:00").is_err());
+        assert!(TrackingSchedule::parse("someday 09:00-10:00").is_err());
+        assert!(TrackingSchedule::parse("mon 9-10").is_err());
+    }
+}
@@ -1,9 +1,12 @@
+use crate::encryption::{EncryptionError, SALT_LENGTH, SessionCipher};
+use crate::session_store::SessionStore;
 use crate::tracker::SerializedSession;
 use serde::ser::Error;
-use serde_json;
+use std::ffi::OsString;
 use std::fs;
 use std::io::{self, Write};
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 #[derive(Debug, thiserror::Error)]
 pub enum PersistenceError {
@@ -13,15 +16,118 @@ pub enum PersistenceError {
     JsonSerialization(#[from] serde_json::Error),
     #[error("Session not found: {0}")]
     SessionNotFound(String),
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
+    #[error("Session {0} is encrypted and no passphrase was provided")]
+    EncryptionKeyRequired(String),
+    #[error("MessagePack encoding error: {0}")]
+    MessagePackEncode(#[from] rmp_serde::encode::Error),
+    #[error("MessagePack decoding error: {0}")]
+    MessagePackDecode(#[from] rmp_serde::decode::Error),
+}
+
+impl PersistenceError {
+    /// Whether retrying the same operation may succeed, e.g. a timeout on a network mount.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            PersistenceError::Io(e) if matches!(
+                e.kind(),
+                io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+            )
+        )
+    }
 }
 
 type Result<T> = std::result::Result<T, PersistenceError>;
 
+const DATA_DIRECTORY_ENV: &str = "BROWSER_TIMER_DATA_DIR";
+
+/// Base directory for everything the host writes: `BROWSER_TIMER_DATA_DIR` when set,
+/// otherwise `browser_timer` under the platform data (or home) directory.
+pub fn data_directory() -> Result<PathBuf> {
+    resolve_data_directory(std::env::var_os(DATA_DIRECTORY_ENV))
+}
+
+pub fn log_directory() -> Result<PathBuf> {
+    Ok(data_directory()?.join("logs"))
+}
+
+fn resolve_data_directory(override_directory: Option<OsString>) -> Result<PathBuf> {
+    if let Some(directory) = override_directory.filter(|directory| !directory.is_empty()) {
+        return Ok(PathBuf::from(directory));
+    }
+    let mut path = dirs::data_dir().or_else(dirs::home_dir).ok_or_else(|| {
+        PersistenceError::Io(io::Error::new(
+            io::ErrorKind::NotFound,
+            "Could not determine default data directory",
+        ))
+    })?;
+    path.push("browser_timer");
+    Ok(path)
+}
+
+pub struct SessionListing {
+    pub sessions: Vec<String>,
+    pub skipped: Vec<String>,
+    /// Session files whose names are not valid UTF-8, by their lossy name (invalid bytes
+    /// become U+FFFD). They cannot be loaded, but `delete_session` accepts the lossy name.
+    pub non_canonical: Vec<String>,
+}
+
+/// On-disk encoding of session files. Either format is read regardless of the one
+/// selected, which only decides how sessions are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SessionFormat {
+    #[default]
+    Json,
+    MessagePack,
+}
+
+impl SessionFormat {
+    const ALL: [SessionFormat; 2] = [SessionFormat::Json, SessionFormat::MessagePack];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "json" => Some(SessionFormat::Json),
+            "msgpack" | "messagepack" | "mp" => Some(SessionFormat::MessagePack),
+            _ => None,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            SessionFormat::Json => "json",
+            SessionFormat::MessagePack => "mp",
+        }
+    }
+
+    fn encode(self, session: &SerializedSession) -> Result<Vec<u8>> {
+        Ok(match self {
+            SessionFormat::Json => serde_json::to_vec(session)?,
+            // Named fields keep the flattened and optional fields of the session decodable.
+            SessionFormat::MessagePack => rmp_serde::to_vec_named(session)?,
+        })
+    }
+
+    fn decode(self, bytes: &[u8]) -> Result<SerializedSession> {
+        Ok(match self {
+            SessionFormat::Json => serde_json::from_slice(bytes)?,
+            SessionFormat::MessagePack => rmp_serde::from_slice(bytes)?,
+        })
+    }
+}
+
 pub struct SessionLoader {
     save_directory: PathBuf,
+    cipher: Option<SessionCipher>,
+    strict_name_check: bool,
+    format: SessionFormat,
 }
 
 impl SessionLoader {
+    /// Leaves the temp files of interrupted saves to `recover_temp_files`, which the host
+    /// runs at startup and read-only uses such as the CLI skip.
     pub fn new<P: AsRef<Path>>(save_directory: P) -> Result<Self> {
         let save_dir = save_directory.as_ref();
 
@@ -31,52 +137,313 @@ impl SessionLoader {
 
         Ok(Self {
             save_directory: save_dir.to_path_buf(),
+            cipher: None,
+            strict_name_check: true,
+            format: SessionFormat::default(),
         })
     }
-    pub fn with_default_directory() -> Result<Self> {
-        let default_dir = Self::default_save_directory()?;
-        Self::new(default_dir)
+
+    /// When disabled, a file whose stored `session_name` differs from the requested one is
+    /// still loaded and adopts the requested name, so renamed backups can be restored as is.
+    pub fn with_strict_name_check(mut self, strict_name_check: bool) -> Self {
+        self.strict_name_check = strict_name_check;
+        self
     }
-    fn default_save_directory() -> Result<PathBuf> {
-        let mut path = dirs::data_dir()
-            .or_else(|| dirs::home_dir())
-            .ok_or_else(|| {
-                PersistenceError::Io(io::Error::new(
-                    io::ErrorKind::NotFound,
-                    "Could not determine default save directory",
-                ))
-            })?;
 
-        path.push("browser_timer");
-        path.push("sessions");
-        Ok(path)
+    /// Writes sessions saved from now on as `{session}.json` or `{session}.mp`, replacing
+    /// a copy in the other format on the next save.
+    pub fn with_format(mut self, format: SessionFormat) -> Self {
+        self.format = format;
+        self
     }
 
-    fn session_file_path(&self, session_name: &str) -> PathBuf {
-        let mut path = self.save_directory.clone();
-        path.push(format!("{}.json", session_name));
-        path
+    /// Encrypts sessions saved from now on into `{session}.json.enc` (or `.mp.enc`),
+    /// replacing any plaintext copy. The key derivation salt is kept next to the sessions.
+    pub fn with_passphrase(mut self, passphrase: &str) -> Result<Self> {
+        let salt_path = self.save_directory.join("encryption.salt");
+        let salt = match fs::read(&salt_path) {
+            Ok(salt) if salt.len() == SALT_LENGTH => salt,
+            Ok(_) => {
+                return Err(PersistenceError::Io(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Corrupted encryption salt file",
+                )));
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                let salt = SessionCipher::generate_salt().to_vec();
+                fs::write(&salt_path, &salt)?;
+                salt
+            }
+            Err(e) => return Err(e.into()),
+        };
+        self.cipher = Some(SessionCipher::from_passphrase(passphrase, &salt)?);
+        Ok(self)
     }
 
-    pub fn save_session(&self, session: &SerializedSession) -> Result<()> {
-        let file_path = self.session_file_path(&session.session_name);
-        let json_data = serde_json::to_string(session)?;
-        let temp_file_path = file_path.with_extension("json.tmp");
+    /// Cleans up the `.tmp` files left behind when a save was interrupted before its
+    /// rename. A temp file that parses replaces a missing or unreadable session and is
+    /// discarded otherwise. Encrypted temp files are only checked once a passphrase is
+    /// set, and kept until then. Returns the names of the promoted sessions.
+    pub fn recover_temp_files(&self) -> Result<Vec<String>> {
+        let mut promoted = Vec::new();
+        for entry in fs::read_dir(&self.save_directory)? {
+            let temp_path = entry?.path();
+            let Some(file_name) = temp_path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".tmp"))
+            else {
+                continue;
+            };
+            let (file_name, encrypted) = match file_name.strip_suffix(".enc") {
+                Some(plain_name) => (plain_name, true),
+                None => (file_name, false),
+            };
+            let Some((session_name, format)) = Self::split_format(file_name) else {
+                continue;
+            };
+            if encrypted && self.cipher.is_none() {
+                continue;
+            }
+            let target_path = if encrypted {
+                self.encrypted_file_path(session_name, format)
+            } else {
+                self.session_file_path(session_name, format)
+            };
+            if self.parses(&temp_path, format, encrypted)
+                && !self.parses(&target_path, format, encrypted)
+            {
+                fs::rename(&temp_path, &target_path)?;
+                promoted.push(session_name.to_string());
+            } else {
+                fs::remove_file(&temp_path)?;
+            }
+        }
+        Ok(promoted)
+    }
+
+    fn parses(&self, path: &Path, format: SessionFormat, encrypted: bool) -> bool {
+        let Ok(bytes) = fs::read(path) else {
+            return false;
+        };
+        let bytes = match (&self.cipher, encrypted) {
+            (Some(cipher), true) => match cipher.decrypt(&bytes) {
+                Ok(bytes) => bytes,
+                Err(_) => return false,
+            },
+            (None, true) => return false,
+            (_, false) => bytes,
+        };
+        format.decode(&bytes).is_ok()
+    }
+    pub fn default_save_directory() -> Result<PathBuf> {
+        Ok(data_directory()?.join("sessions"))
+    }
+
+    fn session_file_path(&self, session_name: &str, format: SessionFormat) -> PathBuf {
+        self.save_directory
+            .join(format!("{}.{}", session_name, format.extension()))
+    }
+
+    fn encrypted_file_path(&self, session_name: &str, format: SessionFormat) -> PathBuf {
+        self.save_directory
+            .join(format!("{}.{}.enc", session_name, format.extension()))
+    }
+
+    /// Every file a session may be stored in, the ones the loader writes first.
+    fn candidate_file_paths(&self, session_name: &str) -> Vec<PathBuf> {
+        let mut formats = vec![self.format];
+        formats.extend(SessionFormat::ALL.into_iter().filter(|f| *f != self.format));
+        formats
+            .into_iter()
+            .flat_map(|format| {
+                [
+                    self.encrypted_file_path(session_name, format),
+                    self.session_file_path(session_name, format),
+                ]
+            })
+            .collect()
+    }
+
+    fn existing_file_path(&self, session_name: &str) -> Option<PathBuf> {
+        self.candidate_file_paths(session_name)
+            .into_iter()
+            .find(|path| path.exists())
+    }
+
+    fn is_session_file(path: &Path) -> bool {
+        let is_session = path.file_name().is_some_and(|name| {
+            Self::session_name_of(&name.to_string_lossy()).is_some_and(|name| !name.is_empty())
+        });
+        path.is_file() && is_session
+    }
+
+    /// Splits `{session}.json` or `{session}.mp` into the session name and its format.
+    fn split_format(file_name: &str) -> Option<(&str, SessionFormat)> {
+        SessionFormat::ALL.into_iter().find_map(|format| {
+            let session_name = file_name
+                .strip_suffix(format.extension())?
+                .strip_suffix('.')?;
+            Some((session_name, format))
+        })
+    }
+
+    fn session_name_of(file_name: &str) -> Option<&str> {
+        let file_name = file_name.strip_suffix(".enc").unwrap_or(file_name);
+        Self::split_format(file_name).map(|(session_name, _)| session_name)
+    }
+
+    fn non_canonical_file_paths(&self, lossy_name: &str) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(&self.save_directory)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name() else {
+                continue;
+            };
+            if file_name.to_str().is_none()
+                && Self::is_session_file(&path)
+                && Self::session_name_of(&file_name.to_string_lossy()) == Some(lossy_name)
+            {
+                paths.push(path);
+            }
+        }
+        Ok(paths)
+    }
+
+    fn write_atomically(file_path: &Path, contents: &[u8]) -> Result<()> {
+        let mut temp_file_path = file_path.as_os_str().to_owned();
+        temp_file_path.push(".tmp");
         {
             let mut file = fs::File::create(&temp_file_path)?;
-            file.write_all(json_data.as_bytes())?;
+            file.write_all(contents)?;
             file.sync_all()?;
         }
         fs::rename(temp_file_path, file_path)?;
         Ok(())
     }
 
-    pub fn load_session(&self, session_name: &str) -> Result<SerializedSession> {
-        let file_path = self.session_file_path(session_name);
-        if !file_path.exists() {
-            return Err(PersistenceError::SessionNotFound(session_name.to_string()));
+    pub fn get_save_directory(&self) -> &Path {
+        &self.save_directory
+    }
+
+    fn backups_directory_path(&self) -> PathBuf {
+        self.save_directory.join("backups")
+    }
+
+    // Notes are also kept in `notes/{session}.txt` (`.txt.enc` when encrypted), so listing
+    // them does not load every session.
+    fn note_file_path(&self, session_name: &str, encrypted: bool) -> PathBuf {
+        let extension = if encrypted { "txt.enc" } else { "txt" };
+        self.save_directory
+            .join("notes")
+            .join(format!("{}.{}", session_name, extension))
+    }
+
+    fn read_note_file(&self, session_name: &str, encrypted: bool) -> Result<String> {
+        let contents = fs::read(self.note_file_path(session_name, encrypted))?;
+        let contents = match (&self.cipher, encrypted) {
+            (Some(cipher), true) => cipher.decrypt(&contents)?,
+            (None, true) => {
+                return Err(PersistenceError::EncryptionKeyRequired(
+                    session_name.to_string(),
+                ));
+            }
+            (_, false) => contents,
+        };
+        String::from_utf8(contents)
+            .map_err(|e| PersistenceError::Io(io::Error::new(io::ErrorKind::InvalidData, e)))
+    }
+
+    fn write_note(&self, session_name: &str, note: Option<&str>) -> Result<()> {
+        let encrypted = self.cipher.is_some();
+        let file_path = self.note_file_path(session_name, encrypted);
+        let stale_path = self.note_file_path(session_name, !encrypted);
+        if stale_path.exists() {
+            fs::remove_file(stale_path)?;
+        }
+        let Some(note) = note else {
+            if file_path.exists() {
+                fs::remove_file(file_path)?;
+            }
+            return Ok(());
+        };
+        if self.read_note_file(session_name, encrypted).ok().as_deref() == Some(note) {
+            return Ok(());
+        }
+        if let Some(notes_directory) = file_path.parent()
+            && !notes_directory.exists()
+        {
+            fs::create_dir(notes_directory)?;
+        }
+        let contents = match &self.cipher {
+            Some(cipher) => cipher.encrypt(note.as_bytes())?,
+            None => note.as_bytes().to_vec(),
+        };
+        Self::write_atomically(&file_path, &contents)
+    }
+
+    /// Splits a `{session}_{YYYYmmdd}_{HHMMSS}.{json,mp}[.enc]` backup file name into the
+    /// session name and its sortable timestamp.
+    fn parse_backup_name(file_name: &str) -> Option<(&str, String)> {
+        let stem = Self::session_name_of(file_name)?;
+        let mut parts = stem.rsplitn(3, '_');
+        let time = parts.next()?;
+        let date = parts.next()?;
+        let session_name = parts.next()?;
+        let is_timestamp =
+            |part: &str, len| part.len() == len && part.bytes().all(|b| b.is_ascii_digit());
+        if session_name.is_empty() || !is_timestamp(date, 8) || !is_timestamp(time, 6) {
+            return None;
+        }
+        Some((session_name, format!("{}{}", date, time)))
+    }
+}
+
+impl SessionStore for SessionLoader {
+    fn save_session(&self, session: &SerializedSession) -> Result<()> {
+        let data = self.format.encode(session)?;
+        let file_path = match &self.cipher {
+            Some(cipher) => {
+                let file_path = self.encrypted_file_path(&session.session_name, self.format);
+                Self::write_atomically(&file_path, &cipher.encrypt(&data)?)?;
+                file_path
+            }
+            None => {
+                let file_path = self.session_file_path(&session.session_name, self.format);
+                Self::write_atomically(&file_path, &data)?;
+                file_path
+            }
+        };
+        for stale_path in self.candidate_file_paths(&session.session_name) {
+            if stale_path != file_path && stale_path.exists() {
+                fs::remove_file(stale_path)?;
+            }
+        }
+        self.write_note(&session.session_name, session.note.as_deref())
+    }
+
+    fn load_session(&self, session_name: &str) -> Result<SerializedSession> {
+        let file_path = self
+            .existing_file_path(session_name)
+            .ok_or_else(|| PersistenceError::SessionNotFound(session_name.to_string()))?;
+        let file_name = file_path.to_string_lossy();
+        let (data, format) = match file_name.strip_suffix(".enc") {
+            Some(plain_name) => {
+                let cipher = self.cipher.as_ref().ok_or_else(|| {
+                    PersistenceError::EncryptionKeyRequired(session_name.to_string())
+                })?;
+                let format = Self::split_format(plain_name).map(|(_, format)| format);
+                (cipher.decrypt(&fs::read(&file_path)?)?, format)
+            }
+            None => (
+                fs::read(&file_path)?,
+                Self::split_format(&file_name).map(|(_, format)| format),
+            ),
+        };
+        let mut session = format.unwrap_or_default().decode(&data)?;
+        if session.session_name != session_name && !self.strict_name_check {
+            session.session_name = session_name.to_string();
         }
-        let session: SerializedSession = serde_json::from_str(&fs::read_to_string(&file_path)?)?;
         if session.session_name != session_name {
             return Err(PersistenceError::JsonSerialization(
                 serde_json::Error::custom(format!(
@@ -87,11 +454,13 @@ impl SessionLoader {
         }
         Ok(session)
     }
-    pub fn session_exists(&self, session_name: &str) -> bool {
-        self.session_file_path(session_name).exists()
+    fn session_exists(&self, session_name: &str) -> bool {
+        self.existing_file_path(session_name).is_some()
     }
 
-    pub fn list_sessions(&self) -> Result<Vec<String>> {
+    /// Lists sessions, skipping directory entries that cannot be read or named instead of
+    /// failing the whole listing. Skipped entries are described in `SessionListing::skipped`.
+    fn scan_sessions(&self) -> Result<SessionListing> {
         if !self.save_directory.exists() {
             return Err(PersistenceError::Io(io::Error::new(
                 io::ErrorKind::NotFound,
@@ -100,45 +469,78 @@ impl SessionLoader {
         }
 
         let entries = fs::read_dir(&self.save_directory)?;
-        let mut sessions = Vec::with_capacity(entries.size_hint().0);
+        let mut listing = SessionListing {
+            sessions: Vec::with_capacity(entries.size_hint().0),
+            skipped: Vec::new(),
+            non_canonical: Vec::new(),
+        };
         for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    listing.skipped.push(e.to_string());
+                    continue;
+                }
+            };
 
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "json") {
-                if let Some(file_stem) = path.file_stem() {
-                    if let Some(session_name) = file_stem.to_str() {
-                        sessions.push(session_name.to_string());
+            if !Self::is_session_file(&path) {
+                continue;
+            }
+            let Some(file_name) = path.file_name() else {
+                listing
+                    .skipped
+                    .push(format!("Unreadable name: {}", path.to_string_lossy()));
+                continue;
+            };
+            match file_name.to_str() {
+                Some(name) => {
+                    if let Some(session_name) = Self::session_name_of(name) {
+                        listing.sessions.push(session_name.to_string());
+                    }
+                }
+                None => {
+                    if let Some(session_name) = Self::session_name_of(&file_name.to_string_lossy())
+                    {
+                        listing.non_canonical.push(session_name.to_string());
                     }
                 }
             }
         }
 
-        sessions.sort_unstable();
-        Ok(sessions)
+        listing.sessions.sort_unstable();
+        listing.sessions.dedup();
+        listing.non_canonical.sort_unstable();
+        listing.non_canonical.dedup();
+        Ok(listing)
     }
 
-    pub fn delete_session(&self, session_name: &str) -> Result<()> {
-        let file_path = self.session_file_path(session_name);
-        if !file_path.exists() {
+    fn delete_session(&self, session_name: &str) -> Result<()> {
+        let mut found = false;
+        while let Some(file_path) = self.existing_file_path(session_name) {
+            fs::remove_file(file_path)?;
+            found = true;
+        }
+        if !found && self.save_directory.exists() {
+            for file_path in self.non_canonical_file_paths(session_name)? {
+                fs::remove_file(file_path)?;
+                found = true;
+            }
+        }
+        if !found {
             return Err(PersistenceError::SessionNotFound(session_name.to_string()));
         }
-        fs::remove_file(file_path)?;
-        Ok(())
-    }
-
-    pub fn get_save_directory(&self) -> &Path {
-        &self.save_directory
+        self.write_note(session_name, None)
     }
 
-    pub fn backup_session(&self, session_name: &str) -> Result<PathBuf> {
-        let file_path = self.session_file_path(session_name);
-        if !file_path.exists() {
-            return Err(PersistenceError::SessionNotFound(session_name.to_string()));
-        }
+    fn backup_session(&self, session_name: &str) -> Result<PathBuf> {
+        let file_path = self
+            .existing_file_path(session_name)
+            .ok_or_else(|| PersistenceError::SessionNotFound(session_name.to_string()))?;
+        let file_name = file_path.file_name().unwrap_or_default().to_string_lossy();
+        let extension = &file_name[session_name.len()..];
         let timestamp = chrono::Utc::now().format("%Y%m%d_%H%M%S");
-        let backup_name = format!("{}_{}.json", session_name, timestamp);
-        let mut backup_path = self.save_directory.join("backups");
+        let backup_name = format!("{}_{}{}", session_name, timestamp, extension);
+        let mut backup_path = self.backups_directory_path();
         if !backup_path.exists() {
             fs::create_dir(&backup_path)?;
         }
@@ -146,6 +548,64 @@ impl SessionLoader {
         fs::copy(&file_path, &backup_path)?;
         Ok(backup_path)
     }
+
+    fn session_note(&self, session_name: &str) -> Result<Option<String>> {
+        for encrypted in [true, false] {
+            if self.note_file_path(session_name, encrypted).exists() {
+                return self.read_note_file(session_name, encrypted).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn session_size(&self, session_name: &str) -> Result<u64> {
+        let file_path = self
+            .existing_file_path(session_name)
+            .ok_or_else(|| PersistenceError::SessionNotFound(session_name.to_string()))?;
+        Ok(fs::metadata(file_path)?.len())
+    }
+
+    fn modified_at(&self, session_name: &str) -> Result<Option<SystemTime>> {
+        match self.existing_file_path(session_name) {
+            Some(file_path) => Ok(Some(fs::metadata(file_path)?.modified()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn save_directory(&self) -> Option<&Path> {
+        Some(&self.save_directory)
+    }
+
+    fn backups_directory(&self) -> Option<PathBuf> {
+        Some(self.backups_directory_path())
+    }
+
+    fn latest_backup(&self) -> Result<Option<(String, PathBuf)>> {
+        let backups_directory = self.backups_directory_path();
+        if !backups_directory.exists() {
+            return Ok(None);
+        }
+        let mut latest: Option<(String, String, PathBuf)> = None;
+        for entry in fs::read_dir(backups_directory)? {
+            let path = entry?.path();
+            if !Self::is_session_file(&path) {
+                continue;
+            }
+            let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            let Some((session_name, timestamp)) = Self::parse_backup_name(file_name) else {
+                continue;
+            };
+            if latest
+                .as_ref()
+                .is_none_or(|(latest_timestamp, _, _)| timestamp > *latest_timestamp)
+            {
+                latest = Some((timestamp, session_name.to_string(), path));
+            }
+        }
+        Ok(latest.map(|(_, session_name, path)| (session_name, path)))
+    }
 }
 
 #[cfg(test)]
@@ -164,12 +624,25 @@ mod tests {
                 aggregate_time: 5000,
                 instances: Some(vec![TabInstance::new(1, 1234)]),
                 children: HashMap::new(),
+                last_url: None,
+                daily: Default::default(),
+                focus_count: 0,
+                interactive_time: 0,
+                engagements: Vec::new(),
+                open_engagement: None,
+                extra: Default::default(),
             },
         );
 
         SerializedSession {
             session_name: "test_session".to_string(),
             data,
+            read_only: false,
+            paused: false,
+            switch_count: 0,
+            evicted_nodes: 0,
+            note: None,
+            extra: Default::default(),
         }
     }
 
@@ -258,6 +731,24 @@ mod tests {
         assert!(!persistence.session_exists("test_session"));
     }
 
+    #[test]
+    fn test_set_read_only_persists_flag() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        persistence.save_session(&create_test_session()).unwrap();
+
+        persistence.set_read_only("test_session", true).unwrap();
+        assert!(persistence.load_session("test_session").unwrap().read_only);
+
+        persistence.set_read_only("test_session", false).unwrap();
+        assert!(!persistence.load_session("test_session").unwrap().read_only);
+
+        assert!(matches!(
+            persistence.set_read_only("nonexistent", true),
+            Err(PersistenceError::SessionNotFound(_))
+        ));
+    }
+
     #[test]
     fn test_backup_session() {
         let temp_dir = TempDir::new().unwrap();
@@ -278,4 +769,455 @@ mod tests {
                 .starts_with("test_session_")
         );
     }
+
+    #[test]
+    fn test_latest_backup_across_sessions() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        assert!(persistence.latest_backup().unwrap().is_none());
+
+        let backups = temp_dir.path().join("backups");
+        fs::create_dir(&backups).unwrap();
+        for file_name in [
+            "work_20250101_120000.json",
+            "my_notes_20250302_080000.json.enc",
+            "work_20250301_235959.json",
+            "not_a_backup.json",
+        ] {
+            fs::write(backups.join(file_name), "{}").unwrap();
+        }
+
+        let (session_name, path) = persistence.latest_backup().unwrap().unwrap();
+        assert_eq!(session_name, "my_notes");
+        assert_eq!(path, backups.join("my_notes_20250302_080000.json.enc"));
+    }
+
+    #[test]
+    fn test_delete_sessions_matching_only_removes_matches() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        for name in ["test_a", "test_b", "work", "my_test_c"] {
+            let mut session = create_test_session();
+            session.session_name = name.to_string();
+            persistence.save_session(&session).unwrap();
+        }
+        persistence.backup_session("test_a").unwrap();
+
+        let deleted = persistence.delete_sessions_matching("test_*").unwrap();
+
+        assert_eq!(deleted, vec!["test_a".to_string(), "test_b".to_string()]);
+        assert_eq!(
+            persistence.list_sessions().unwrap(),
+            vec!["my_test_c".to_string(), "work".to_string()]
+        );
+        assert_eq!(
+            fs::read_dir(temp_dir.path().join("backups"))
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_recover_promotes_temp_without_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let json = serde_json::to_string(&create_test_session()).unwrap();
+        fs::write(temp_dir.path().join("test_session.json.tmp"), json).unwrap();
+
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        assert_eq!(
+            persistence.recover_temp_files().unwrap(),
+            vec!["test_session"]
+        );
+
+        assert!(persistence.session_exists("test_session"));
+        assert!(!temp_dir.path().join("test_session.json.tmp").exists());
+        let loaded = persistence.load_session("test_session").unwrap();
+        assert_eq!(loaded.data["example.com"].aggregate_time, 5000);
+    }
+
+    #[test]
+    fn test_new_leaves_temp_files_alone() {
+        let temp_dir = TempDir::new().unwrap();
+        let temp_path = temp_dir.path().join("test_session.json.tmp");
+        fs::write(
+            &temp_path,
+            serde_json::to_string(&create_test_session()).unwrap(),
+        )
+        .unwrap();
+
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+
+        assert!(temp_path.exists());
+        assert!(!persistence.session_exists("test_session"));
+    }
+
+    #[test]
+    fn test_recover_deletes_stale_temp_next_to_valid_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        persistence.save_session(&create_test_session()).unwrap();
+        let mut newer = create_test_session();
+        newer.data.clear();
+        fs::write(
+            temp_dir.path().join("test_session.json.tmp"),
+            serde_json::to_string(&newer).unwrap(),
+        )
+        .unwrap();
+        fs::write(temp_dir.path().join("broken.json.tmp"), "{\"session_na").unwrap();
+
+        assert!(persistence.recover_temp_files().unwrap().is_empty());
+
+        assert!(!temp_dir.path().join("test_session.json.tmp").exists());
+        assert!(!temp_dir.path().join("broken.json.tmp").exists());
+        assert!(!persistence.session_exists("broken"));
+        let loaded = persistence.load_session("test_session").unwrap();
+        assert_eq!(loaded.data.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_sessions_surfaces_non_utf8_names() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        persistence.save_session(&create_test_session()).unwrap();
+        let bad_name = OsStr::from_bytes(b"bad\xff.json");
+        if fs::write(temp_dir.path().join(bad_name), "{}").is_err() {
+            return; // filesystem rejects non-UTF-8 names
+        }
+
+        let listing = persistence.scan_sessions().unwrap();
+
+        assert_eq!(listing.sessions, vec!["test_session".to_string()]);
+        assert!(listing.skipped.is_empty());
+        assert_eq!(listing.non_canonical, vec!["bad\u{FFFD}".to_string()]);
+        assert_eq!(
+            persistence.list_sessions().unwrap(),
+            vec!["test_session".to_string()]
+        );
+
+        persistence.delete_session("bad\u{FFFD}").unwrap();
+        assert!(!temp_dir.path().join(bad_name).exists());
+        assert!(
+            persistence
+                .scan_sessions()
+                .unwrap()
+                .non_canonical
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_data_directory_override() {
+        let overridden = resolve_data_directory(Some(OsString::from("/srv/timer"))).unwrap();
+        assert_eq!(overridden, PathBuf::from("/srv/timer"));
+
+        if let Ok(default) = resolve_data_directory(Some(OsString::new())) {
+            assert!(default.ends_with("browser_timer"));
+        }
+        if let (Ok(data), Ok(logs)) = (data_directory(), log_directory()) {
+            assert_eq!(logs, data.join("logs"));
+            assert_eq!(
+                SessionLoader::default_save_directory().unwrap(),
+                data.join("sessions")
+            );
+        }
+    }
+
+    #[test]
+    fn test_encrypted_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_passphrase("hunter2")
+            .unwrap();
+        persistence.save_session(&create_test_session()).unwrap();
+
+        let encrypted = fs::read(temp_dir.path().join("test_session.json.enc")).unwrap();
+        assert!(!String::from_utf8_lossy(&encrypted).contains("example.com"));
+        assert!(!temp_dir.path().join("test_session.json").exists());
+        assert_eq!(
+            persistence.list_sessions().unwrap(),
+            vec!["test_session".to_string()]
+        );
+
+        let reopened = SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_passphrase("hunter2")
+            .unwrap();
+        let loaded = reopened.load_session("test_session").unwrap();
+        assert_eq!(loaded.data["example.com"].aggregate_time, 5000);
+    }
+
+    #[test]
+    fn test_note_is_kept_next_to_the_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        let mut session = create_test_session();
+        session.note = Some("exam prep week".to_string());
+        persistence.save_session(&session).unwrap();
+
+        let note_path = temp_dir.path().join("notes").join("test_session.txt");
+        assert_eq!(fs::read_to_string(&note_path).unwrap(), "exam prep week");
+        // The note is answered from its own file.
+        fs::write(temp_dir.path().join("test_session.json"), b"not json").unwrap();
+        assert_eq!(
+            persistence.session_note("test_session").unwrap().as_deref(),
+            Some("exam prep week")
+        );
+
+        let encrypted = SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_passphrase("hunter2")
+            .unwrap();
+        encrypted.save_session(&session).unwrap();
+        assert!(!note_path.exists());
+        let encrypted_note = temp_dir.path().join("notes").join("test_session.txt.enc");
+        assert!(!String::from_utf8_lossy(&fs::read(&encrypted_note).unwrap()).contains("exam"));
+        assert_eq!(
+            encrypted.session_note("test_session").unwrap().as_deref(),
+            Some("exam prep week")
+        );
+        assert!(matches!(
+            persistence.session_note("test_session"),
+            Err(PersistenceError::EncryptionKeyRequired(_))
+        ));
+
+        session.note = None;
+        encrypted.save_session(&session).unwrap();
+        assert!(!encrypted_note.exists());
+        session.note = Some("again".to_string());
+        encrypted.save_session(&session).unwrap();
+        encrypted.delete_session("test_session").unwrap();
+        assert_eq!(encrypted.session_note("test_session").unwrap(), None);
+    }
+
+    #[test]
+    fn test_recover_encrypted_temp_files_with_the_passphrase() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_format(SessionFormat::MessagePack)
+            .with_passphrase("hunter2")
+            .unwrap();
+        persistence.save_session(&create_test_session()).unwrap();
+        let saved_path = temp_dir.path().join("test_session.mp.enc");
+        let temp_path = temp_dir.path().join("test_session.mp.enc.tmp");
+        fs::rename(&saved_path, &temp_path).unwrap();
+        fs::write(
+            temp_dir.path().join("broken.json.enc.tmp"),
+            b"not encrypted",
+        )
+        .unwrap();
+
+        // Without the passphrase the temp files cannot be checked, so they are kept.
+        let no_key = SessionLoader::new(temp_dir.path()).unwrap();
+        assert!(no_key.recover_temp_files().unwrap().is_empty());
+        assert!(temp_path.exists());
+
+        assert_eq!(
+            persistence.recover_temp_files().unwrap(),
+            vec!["test_session"]
+        );
+        assert!(!temp_path.exists());
+        assert!(!temp_dir.path().join("broken.json.enc.tmp").exists());
+        let loaded = persistence.load_session("test_session").unwrap();
+        assert_eq!(loaded.data["example.com"].aggregate_time, 5000);
+    }
+
+    #[test]
+    fn test_encrypted_session_needs_right_key() {
+        let temp_dir = TempDir::new().unwrap();
+        SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_passphrase("hunter2")
+            .unwrap()
+            .save_session(&create_test_session())
+            .unwrap();
+
+        let wrong_key = SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_passphrase("hunter3")
+            .unwrap();
+        assert!(matches!(
+            wrong_key.load_session("test_session"),
+            Err(PersistenceError::Encryption(EncryptionError::Decrypt))
+        ));
+
+        let no_key = SessionLoader::new(temp_dir.path()).unwrap();
+        assert!(matches!(
+            no_key.load_session("test_session"),
+            Err(PersistenceError::EncryptionKeyRequired(_))
+        ));
+        no_key.delete_session("test_session").unwrap();
+        assert!(!no_key.session_exists("test_session"));
+    }
+
+    #[test]
+    fn test_message_pack_round_trip_matches_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let json = SessionLoader::new(temp_dir.path().join("json")).unwrap();
+        let binary = SessionLoader::new(temp_dir.path().join("binary"))
+            .unwrap()
+            .with_format(SessionFormat::MessagePack);
+        let mut session = create_test_session();
+        session.note = Some("round trip".to_string());
+        session
+            .extra
+            .insert("future_field".to_string(), serde_json::json!([1, "two"]));
+        json.save_session(&session).unwrap();
+        binary.save_session(&session).unwrap();
+
+        assert!(binary.get_save_directory().join("test_session.mp").exists());
+        assert!(
+            !binary
+                .get_save_directory()
+                .join("test_session.json")
+                .exists()
+        );
+        assert_eq!(
+            binary.list_sessions().unwrap(),
+            vec!["test_session".to_string()]
+        );
+        let from_json = json.load_session("test_session").unwrap();
+        let from_binary = binary.load_session("test_session").unwrap();
+        assert_eq!(
+            serde_json::to_value(&from_binary).unwrap(),
+            serde_json::to_value(&from_json).unwrap()
+        );
+
+        // Either format is read, and the next save replaces the file in the old one.
+        let json_again = SessionLoader::new(binary.get_save_directory()).unwrap();
+        let loaded = json_again.load_session("test_session").unwrap();
+        assert_eq!(loaded.data["example.com"].aggregate_time, 5000);
+        json_again.save_session(&loaded).unwrap();
+        assert!(
+            binary
+                .get_save_directory()
+                .join("test_session.json")
+                .exists()
+        );
+        assert!(!binary.get_save_directory().join("test_session.mp").exists());
+    }
+
+    #[test]
+    fn test_unusual_tree_keys_round_trip_exactly() {
+        let keys = [
+            "caf\u{e9}.example",
+            "\u{1f600}\u{1f3f3}\u{fe0f}\u{200d}\u{1f308}",
+            "tab\there\nnewline\u{0}nul\u{1b}[31m\u{7f}",
+            "\"quoted\" \\back\\slash",
+            "../../escape",
+            "a/b",
+            "\u{202e}rtl\u{feff}",
+        ];
+        let leaf = |key: &str, aggregate_time: u64| SerializedUrlNode {
+            sub_part: key.to_string(),
+            aggregate_time,
+            instances: None,
+            children: HashMap::new(),
+            last_url: None,
+            daily: Default::default(),
+            focus_count: 0,
+            interactive_time: 0,
+            engagements: Vec::new(),
+            open_engagement: None,
+            extra: Default::default(),
+        };
+        let mut session = create_test_session();
+        let parent = session.data.get_mut("example.com").unwrap();
+        for (time, key) in keys.iter().enumerate() {
+            parent
+                .children
+                .insert(key.to_string(), leaf(key, time as u64));
+        }
+        session.data.insert(keys[1].to_string(), leaf(keys[1], 42));
+
+        let temp_dir = TempDir::new().unwrap();
+        for format in [SessionFormat::Json, SessionFormat::MessagePack] {
+            let directory = temp_dir.path().join(format.extension());
+            let persistence = SessionLoader::new(&directory).unwrap().with_format(format);
+            persistence.save_session(&session).unwrap();
+            let loaded = persistence.load_session("test_session").unwrap();
+
+            assert_eq!(
+                serde_json::to_value(&loaded).unwrap(),
+                serde_json::to_value(&session).unwrap()
+            );
+            let children = &loaded.data["example.com"].children;
+            for key in keys {
+                assert_eq!(children[key].sub_part, key);
+            }
+            // Only the session name ends up in a file name.
+            let files: Vec<_> = fs::read_dir(&directory)
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name())
+                .collect();
+            assert_eq!(
+                files,
+                vec![std::ffi::OsString::from(format!(
+                    "test_session.{}",
+                    format.extension()
+                ))]
+            );
+        }
+    }
+
+    #[test]
+    fn test_encrypted_message_pack_session() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_format(SessionFormat::MessagePack)
+            .with_passphrase("hunter2")
+            .unwrap();
+        persistence.save_session(&create_test_session()).unwrap();
+        assert!(temp_dir.path().join("test_session.mp.enc").exists());
+
+        let backup = persistence.backup_session("test_session").unwrap();
+        assert!(backup.to_string_lossy().ends_with(".mp.enc"));
+        assert_eq!(
+            persistence.latest_backup().unwrap().unwrap().0,
+            "test_session"
+        );
+        let loaded = persistence.load_session("test_session").unwrap();
+        assert_eq!(loaded.data["example.com"].aggregate_time, 5000);
+    }
+
+    #[test]
+    fn test_parse_session_format() {
+        assert_eq!(SessionFormat::parse("JSON"), Some(SessionFormat::Json));
+        assert_eq!(
+            SessionFormat::parse("msgpack"),
+            Some(SessionFormat::MessagePack)
+        );
+        assert_eq!(SessionFormat::parse("bincode"), None);
+    }
+
+    #[test]
+    fn test_name_mismatch_strict_and_lenient() {
+        let temp_dir = TempDir::new().unwrap();
+        let persistence = SessionLoader::new(temp_dir.path()).unwrap();
+        persistence.save_session(&create_test_session()).unwrap();
+        fs::copy(
+            temp_dir.path().join("test_session.json"),
+            temp_dir.path().join("restored.json"),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            persistence.load_session("restored"),
+            Err(PersistenceError::JsonSerialization(_))
+        ));
+
+        let lenient = SessionLoader::new(temp_dir.path())
+            .unwrap()
+            .with_strict_name_check(false);
+        let loaded = lenient.load_session("restored").unwrap();
+        assert_eq!(loaded.session_name, "restored");
+        assert_eq!(loaded.data["example.com"].aggregate_time, 5000);
+    }
 }
@@ -0,0 +1,236 @@
+use crate::session_loader::{PersistenceError, SessionListing};
+use crate::tracker::SerializedSession;
+use std::path::{Path, PathBuf};
+
+type Result<T> = std::result::Result<T, PersistenceError>;
+
+/// Persistence backend used by the messaging host. `SessionLoader` is the filesystem
+/// implementation.
+pub trait SessionStore: Send + Sync {
+    fn save_session(&self, session: &SerializedSession) -> Result<()>;
+
+    fn load_session(&self, session_name: &str) -> Result<SerializedSession>;
+
+    fn session_exists(&self, session_name: &str) -> bool;
+
+    fn scan_sessions(&self) -> Result<SessionListing>;
+
+    fn delete_session(&self, session_name: &str) -> Result<()>;
+
+    fn backup_session(&self, session_name: &str) -> Result<PathBuf>;
+
+    /// Size in bytes of the stored session.
+    fn session_size(&self, session_name: &str) -> Result<u64>;
+
+    /// When the stored session was last written, for stores that can tell.
+    fn modified_at(&self, _session_name: &str) -> Result<Option<std::time::SystemTime>> {
+        Ok(None)
+    }
+
+    /// Where sessions are stored, for stores backed by the filesystem.
+    fn save_directory(&self) -> Option<&Path> {
+        None
+    }
+
+    fn backups_directory(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// Newest backup of any session, as the session name and where the backup is.
+    fn latest_backup(&self) -> Result<Option<(String, PathBuf)>> {
+        Ok(None)
+    }
+
+    fn list_sessions(&self) -> Result<Vec<String>> {
+        Ok(self.scan_sessions()?.sessions)
+    }
+
+    fn set_read_only(&self, session_name: &str, read_only: bool) -> Result<()> {
+        let mut session = self.load_session(session_name)?;
+        session.read_only = read_only;
+        self.save_session(&session)
+    }
+
+    /// The note saved with a session. Stores that keep notes apart from the session data
+    /// answer without loading the session.
+    fn session_note(&self, session_name: &str) -> Result<Option<String>> {
+        Ok(self.load_session(session_name)?.note)
+    }
+
+    /// Deletes every session whose name matches `pattern`, where `*` matches any run of
+    /// characters and `?` a single one. Backups are never touched.
+    fn delete_sessions_matching(&self, pattern: &str) -> Result<Vec<String>> {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let mut deleted = Vec::new();
+        for session_name in self.list_sessions()? {
+            let name: Vec<char> = session_name.chars().collect();
+            if glob_matches(&pattern, &name) {
+                self.delete_session(&session_name)?;
+                deleted.push(session_name);
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` matches any run of characters and `?` a
+/// single one.
+pub(crate) fn matches_glob(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    glob_matches(&pattern, &name)
+}
+
+fn glob_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|skip| glob_matches(rest, &name[skip..])),
+        Some((&expected, rest)) => name.split_first().is_some_and(|(&c, name_rest)| {
+            (expected == '?' || expected == c) && glob_matches(rest, name_rest)
+        }),
+    }
+}
+
+/// Keeps sessions as serialized JSON in memory, so they round-trip like files on disk.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct InMemorySessionStore {
+    sessions: std::sync::Mutex<std::collections::BTreeMap<String, String>>,
+    backups: std::sync::Mutex<Vec<(String, String)>>,
+    save_failures: std::sync::Mutex<Vec<std::io::ErrorKind>>,
+}
+
+#[cfg(test)]
+impl InMemorySessionStore {
+    /// Makes the next saves fail with these IO error kinds, in order.
+    pub fn with_save_failures(self, failures: Vec<std::io::ErrorKind>) -> Self {
+        *self.save_failures.lock().unwrap() = failures;
+        self
+    }
+}
+
+#[cfg(test)]
+impl SessionStore for InMemorySessionStore {
+    fn save_session(&self, session: &SerializedSession) -> Result<()> {
+        let mut failures = self.save_failures.lock().unwrap();
+        if !failures.is_empty() {
+            return Err(std::io::Error::from(failures.remove(0)).into());
+        }
+        drop(failures);
+        let json_data = serde_json::to_string(session)?;
+        self.sessions
+            .lock()
+            .unwrap()
+            .insert(session.session_name.clone(), json_data);
+        Ok(())
+    }
+
+    fn load_session(&self, session_name: &str) -> Result<SerializedSession> {
+        let sessions = self.sessions.lock().unwrap();
+        let json_data = sessions
+            .get(session_name)
+            .ok_or_else(|| PersistenceError::SessionNotFound(session_name.to_string()))?;
+        Ok(serde_json::from_str(json_data)?)
+    }
+
+    fn session_exists(&self, session_name: &str) -> bool {
+        self.sessions.lock().unwrap().contains_key(session_name)
+    }
+
+    fn scan_sessions(&self) -> Result<SessionListing> {
+        Ok(SessionListing {
+            sessions: self.sessions.lock().unwrap().keys().cloned().collect(),
+            skipped: Vec::new(),
+            non_canonical: Vec::new(),
+        })
+    }
+
+    fn delete_session(&self, session_name: &str) -> Result<()> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .remove(session_name)
+            .map(|_| ())
+            .ok_or_else(|| PersistenceError::SessionNotFound(session_name.to_string()))
+    }
+
+    fn backup_session(&self, session_name: &str) -> Result<PathBuf> {
+        let json_data = self
+            .sessions
+            .lock()
+            .unwrap()
+            .get(session_name)
+            .cloned()
+            .ok_or_else(|| PersistenceError::SessionNotFound(session_name.to_string()))?;
+        let mut backups = self.backups.lock().unwrap();
+        backups.push((session_name.to_string(), json_data));
+        Ok(PathBuf::from(format!(
+            "memory/backups/{}_{}.json",
+            session_name,
+            backups.len()
+        )))
+    }
+
+    fn session_size(&self, session_name: &str) -> Result<u64> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_name)
+            .map(|json_data| json_data.len() as u64)
+            .ok_or_else(|| PersistenceError::SessionNotFound(session_name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn session(name: &str) -> SerializedSession {
+        SerializedSession {
+            session_name: name.to_string(),
+            data: HashMap::new(),
+            read_only: false,
+            paused: false,
+            switch_count: 0,
+            evicted_nodes: 0,
+            note: None,
+            extra: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        let matches = |pattern: &str, name: &str| {
+            glob_matches(
+                &pattern.chars().collect::<Vec<_>>(),
+                &name.chars().collect::<Vec<_>>(),
+            )
+        };
+        assert!(matches("test_*", "test_"));
+        assert!(matches("*_old", "work_old"));
+        assert!(matches("s?ssion", "session"));
+        assert!(!matches("test_*", "my_test"));
+        assert!(!matches("work", "work2"));
+    }
+
+    #[test]
+    fn test_in_memory_store_default_methods() {
+        let store = InMemorySessionStore::default();
+        for name in ["test_a", "test_b", "keep"] {
+            store.save_session(&session(name)).unwrap();
+        }
+
+        store.set_read_only("keep", true).unwrap();
+        assert!(store.load_session("keep").unwrap().read_only);
+        assert_eq!(
+            store.delete_sessions_matching("test_*").unwrap(),
+            vec!["test_a".to_string(), "test_b".to_string()]
+        );
+        assert_eq!(store.list_sessions().unwrap(), vec!["keep".to_string()]);
+        assert!(matches!(
+            store.load_session("test_a"),
+            Err(PersistenceError::SessionNotFound(_))
+        ));
+    }
+}